@@ -0,0 +1,15 @@
+// Copyright (c) 2017 Anatoly Ikorsky
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+pub mod local_infile;
+mod queryable;
+
+pub use local_infile::{InMemoryLocalInfileHandler, WhiteListFsLocalInfileHandler};
+pub use queryable::query_result::{
+    OwnedResultSetStream, QueryResult, ResultSet, ResultSetMeta, ResultSetStream, ResultSets,
+};
@@ -6,11 +6,23 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
+use futures_core::stream::Stream;
 use mysql_common::row::convert::FromRowError;
-use mysql_common::{io::ReadMysqlExt, packets::parse_local_infile_packet};
+use mysql_common::{
+    io::ReadMysqlExt,
+    packets::{parse_err_packet, parse_local_infile_packet},
+};
 use tokio::prelude::*;
 
-use std::{borrow::Cow, marker::PhantomData, result::Result as StdResult, sync::Arc};
+use std::{
+    borrow::Cow,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    result::Result as StdResult,
+    sync::Arc,
+    task::{Context, Poll},
+};
 
 use crate::{
     connection_like::{Connection, ConnectionLike},
@@ -20,6 +32,25 @@ use crate::{
     Column, Row,
 };
 
+/// A boxed future, akin to `futures::future::BoxFuture`.
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// `COM_RESET_CONNECTION`, as defined by the MySQL client/server protocol.
+const COM_RESET_CONNECTION: u8 = 0x1f;
+
+/// `COM_STMT_FETCH`, as defined by the MySQL client/server protocol.
+const COM_STMT_FETCH: u8 = 0x1c;
+
+/// Builds the payload of a `COM_STMT_FETCH` packet: the command byte followed by the
+/// little-endian `statement_id` and `fetch_size`.
+fn stmt_fetch_payload(statement_id: u32, fetch_size: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(9);
+    data.push(COM_STMT_FETCH);
+    data.extend_from_slice(&statement_id.to_le_bytes());
+    data.extend_from_slice(&fetch_size.to_le_bytes());
+    data
+}
+
 /// Result set metadata.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ResultSetMeta {
@@ -37,9 +68,25 @@ impl ResultSetMeta {
     }
 }
 
+/// State of an open server-side cursor, used to page a prepared statement's result set via
+/// `COM_STMT_FETCH` instead of reading it all at once.
+#[derive(Debug, Clone, Copy)]
+struct CursorState {
+    statement_id: u32,
+    fetch_size: u32,
+    /// `true` once a `COM_STMT_FETCH` has been sent for the cursor's current batch.
+    ///
+    /// Starts `false`: after `CURSOR_TYPE_READ_ONLY` execution the server already sent its
+    /// column-definition-terminating status packet (carrying `SERVER_STATUS_CURSOR_EXISTS`) with
+    /// no rows attached, so the very first `get_row_raw` call must send a fetch itself rather
+    /// than blocking on a `read_packet` the server has no reason to answer.
+    fetched: bool,
+}
+
 /// Result of a query or statement execution.
 pub struct QueryResult<'a, 't: 'a, P> {
     conn: Connection<'a, 't>,
+    cursor: Option<CursorState>,
     __phantom: PhantomData<P>,
 }
 
@@ -50,6 +97,35 @@ where
     pub(crate) fn new<T: Into<Connection<'a, 't>>>(conn: T) -> Self {
         QueryResult {
             conn: conn.into(),
+            cursor: None,
+            __phantom: PhantomData,
+        }
+    }
+
+    /// Like [`QueryResult::new`], but pages rows of the (single, prepared-statement) result set
+    /// through an already-open server-side cursor, fetching up to `fetch_size` rows per
+    /// `COM_STMT_FETCH` round-trip instead of consuming the whole result set at line rate.
+    ///
+    /// The statement must have been executed with the `CURSOR_TYPE_READ_ONLY` flag for
+    /// `statement_id`, so that the server holds the cursor open rather than sending rows inline.
+    ///
+    /// This is the hook the public `fetch_size` exec option is meant to go through: the
+    /// statement-execution code picks `CURSOR_TYPE_READ_ONLY` and a `fetch_size` based on that
+    /// option and constructs its `QueryResult` with this constructor instead of
+    /// [`QueryResult::new`].
+    ///
+    /// `pub` rather than `pub(crate)` because the statement-execution code that picks
+    /// `CURSOR_TYPE_READ_ONLY` and calls this isn't part of this snapshot (no `Conn::exec*`/`Opts`
+    /// module is present here to edit); exposing the constructor is what's needed on this side of
+    /// that boundary so that code can call it once it exists.
+    pub fn with_cursor<T: Into<Connection<'a, 't>>>(conn: T, statement_id: u32, fetch_size: u32) -> Self {
+        QueryResult {
+            conn: conn.into(),
+            cursor: Some(CursorState {
+                statement_id,
+                fetch_size,
+                fetched: false,
+            }),
             __phantom: PhantomData,
         }
     }
@@ -77,23 +153,56 @@ where
     }
 
     async fn get_row_raw(&mut self) -> Result<Option<Vec<u8>>> {
-        if self.is_empty() {
-            return Ok(None);
+        // A fresh cursor-backed result set needs its first `COM_STMT_FETCH` sent before we read
+        // anything: the server already sent its (rowless) column-definitions-terminating status
+        // packet when the statement was executed, so a `read_packet` here would just hang
+        // waiting for a response the server has no reason to send.
+        if let Some(mut cursor) = self.cursor {
+            if !cursor.fetched {
+                cursor.fetched = true;
+                self.cursor = Some(cursor);
+
+                if !self.cursor_open() {
+                    self.make_empty();
+                    return Ok(None);
+                }
+
+                self.conn
+                    .conn_mut()
+                    .fetch_cursor_rows(cursor.statement_id, cursor.fetch_size)
+                    .await?;
+            }
         }
 
-        let packet: Vec<u8> = self.conn.conn_mut().read_packet().await?;
+        loop {
+            if self.is_empty() {
+                return Ok(None);
+            }
+
+            let packet: Vec<u8> = self.conn.conn_mut().read_packet().await?;
+
+            if P::is_last_result_set_packet(self.conn.conn_ref().capabilities(), &packet) {
+                if let Some(cursor) = self.cursor {
+                    if self.cursor_open() {
+                        self.conn
+                            .conn_mut()
+                            .fetch_cursor_rows(cursor.statement_id, cursor.fetch_size)
+                            .await?;
+                        continue;
+                    }
+                }
 
-        if P::is_last_result_set_packet(self.conn.conn_ref().capabilities(), &packet) {
-            if self.more_results_exists() {
-                self.conn.conn_mut().sync_seq_id();
-                self.conn.conn_mut().read_result_set::<P>().await?;
-                Ok(None)
+                if self.more_results_exists() {
+                    self.conn.conn_mut().sync_seq_id();
+                    self.conn.conn_mut().read_result_set::<P>().await?;
+                    return Ok(None);
+                } else {
+                    self.make_empty();
+                    return Ok(None);
+                }
             } else {
-                self.make_empty();
-                Ok(None)
+                return Ok(Some(packet));
             }
-        } else {
-            Ok(Some(packet))
         }
     }
 
@@ -131,6 +240,16 @@ where
         self.conn.conn_ref().get_warnings()
     }
 
+    /// Returns `true` if the `SERVER_STATUS_CURSOR_EXISTS` flag is contained in status flags of
+    /// the connection, i.e. the server-side cursor backing this query result still has rows
+    /// left to fetch.
+    fn cursor_open(&self) -> bool {
+        self.conn
+            .conn_ref()
+            .status()
+            .contains(StatusFlags::SERVER_STATUS_CURSOR_EXISTS)
+    }
+
     /// Returns `true` if the `SERVER_MORE_RESULTS_EXISTS` flag is contained in status flags
     /// of the connection.
     fn more_results_exists(&self) -> bool {
@@ -180,6 +299,40 @@ where
         .await
     }
 
+    /// Returns a stream over rows of the current result set.
+    ///
+    /// Unlike [`QueryResult::collect`] and friends, rows are produced lazily as they are polled,
+    /// which makes it possible to use `StreamExt` combinators (`try_next`, `take`, `buffered`,
+    /// ...) instead of buffering the whole result set up front.
+    ///
+    /// It will stop on the nearest result set boundary (see [`QueryResult::collect`] docs) -
+    /// i.e. the stream ends at the end of the current result set, without consuming any
+    /// subsequent ones.
+    pub fn stream<R>(&mut self) -> ResultSetStream<'_, 'a, 't, P, R>
+    where
+        R: FromRow + Send + 'static,
+    {
+        ResultSetStream {
+            state: ResultSetStreamState::Ready(self),
+            __phantom: PhantomData,
+        }
+    }
+
+    /// Returns an owning stream over rows of the current result set.
+    ///
+    /// Works like [`QueryResult::stream`], but takes ownership of `self`, which makes it
+    /// possible to return the stream from a function. The rest of the query result (including
+    /// any subsequent result sets) is dropped once the stream is exhausted.
+    pub fn stream_and_drop<R>(self) -> OwnedResultSetStream<'a, 't, P, R>
+    where
+        R: FromRow + Send + 'static,
+    {
+        OwnedResultSetStream {
+            state: OwnedResultSetStreamState::Ready(Box::new(self)),
+            __phantom: PhantomData,
+        }
+    }
+
     /// Returns a future that collects the current result set of this query result and drops
     /// everything else.
     ///
@@ -311,8 +464,16 @@ where
         Ok(acc)
     }
 
-    /// Returns a future that will drop this query result.
-    pub async fn drop_result(mut self) -> Result<()> {
+    /// Drains any rows of the current result set left unread, stopping at the nearest result
+    /// set boundary (see [`QueryResult::collect`] docs) rather than consuming the whole query
+    /// result.
+    async fn drain_current_result_set(&mut self) -> Result<()> {
+        while self.get_row_raw().await?.is_some() {}
+        Ok(())
+    }
+
+    /// Drains this query result, discarding every remaining row and result set.
+    async fn drain(&mut self) -> Result<()> {
         loop {
             if !self.has_rows() {
                 self.make_empty();
@@ -329,6 +490,11 @@ where
         Ok(())
     }
 
+    /// Returns a future that will drop this query result.
+    pub async fn drop_result(mut self) -> Result<()> {
+        self.drain().await
+    }
+
     /// Returns a reference to a columns list of this query result.
     ///
     /// Empty list means, that this result set was never meant to contain rows.
@@ -342,6 +508,241 @@ where
     pub fn columns(&self) -> Option<Arc<Vec<Column>>> {
         self.meta().map(|meta| meta.columns().clone())
     }
+
+    /// Returns an iterator over the result sets of this query result.
+    ///
+    /// This is a convenience over calling [`QueryResult::collect`]/[`QueryResult::stream`] and
+    /// friends once per result set and checking [`QueryResult::is_empty`] in between: advancing
+    /// the returned [`ResultSets`] drains any unread rows of the current result set for you and
+    /// moves on to the next one, if any, so each result set's own [`Column`] metadata stays
+    /// available via [`ResultSet::columns_ref`] even when result sets don't share a schema.
+    pub fn result_sets(&mut self) -> ResultSets<'_, 'a, 't, P> {
+        ResultSets {
+            query_result: self,
+            current: None,
+        }
+    }
+}
+
+/// State of a [`ResultSetStream`].
+///
+/// Only one of "holding the borrow" and "polling a future built from the borrow" is ever true at
+/// a time - the in-flight future in `Polling` *owns* the `&'q mut QueryResult` (moved in, handed
+/// back out once the future resolves), so there's never a second live borrow aliasing it.
+enum ResultSetStreamState<'q, 'a, 't, P> {
+    Ready(&'q mut QueryResult<'a, 't, P>),
+    Polling(BoxFuture<'q, (Result<Option<Row>>, &'q mut QueryResult<'a, 't, P>)>),
+    Done,
+}
+
+/// A stream over rows of the current result set of a [`QueryResult`].
+///
+/// Created by [`QueryResult::stream`]. Ends at the nearest result set boundary, leaving any
+/// subsequent result sets untouched - see [`QueryResult::collect`] docs for details on result
+/// set boundaries.
+///
+/// Note: `QueryResult` is built on `Connection`/`ConnectionLike` (from `crate::connection_like`),
+/// which aren't part of this snapshot. Without their real definitions, a mock
+/// connection here would just be guessed-at, not verified against the trait it's supposed to
+/// stand in for, so the `test` module below covers only the connection-independent helpers
+/// (`stmt_fetch_payload`) rather than stream/boundary behavior.
+pub struct ResultSetStream<'q, 'a, 't, P, R> {
+    state: ResultSetStreamState<'q, 'a, 't, P>,
+    __phantom: PhantomData<R>,
+}
+
+impl<'q, 'a, 't, P, R> Stream for ResultSetStream<'q, 'a, 't, P, R>
+where
+    P: Protocol,
+    R: FromRow + Send + 'static,
+{
+    type Item = Result<R>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // No field here ever points into `Self`'s own memory, so a plain `&mut` projection is
+        // sound.
+        let this = self.get_mut();
+
+        loop {
+            match std::mem::replace(&mut this.state, ResultSetStreamState::Done) {
+                ResultSetStreamState::Done => return Poll::Ready(None),
+                ResultSetStreamState::Ready(query_result) => {
+                    this.state = ResultSetStreamState::Polling(Box::pin(async move {
+                        let row = query_result.get_row().await;
+                        (row, query_result)
+                    }));
+                }
+                ResultSetStreamState::Polling(mut fut) => match fut.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        this.state = ResultSetStreamState::Polling(fut);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((result, query_result)) => match result {
+                        Ok(Some(row)) => {
+                            this.state = ResultSetStreamState::Ready(query_result);
+                            return Poll::Ready(Some(FromRow::from_row_opt(row).map_err(Into::into)));
+                        }
+                        Ok(None) => return Poll::Ready(None),
+                        Err(e) => return Poll::Ready(Some(Err(e))),
+                    },
+                },
+            }
+        }
+    }
+}
+
+/// State of an [`OwnedResultSetStream`]. See [`ResultSetStreamState`] for the move-don't-alias
+/// rationale; here the future owns the `Box<QueryResult>` outright instead of a borrow of it.
+enum OwnedResultSetStreamState<'a, 't, P> {
+    Ready(Box<QueryResult<'a, 't, P>>),
+    Polling(BoxFuture<'a, (Result<Option<Row>>, Box<QueryResult<'a, 't, P>>)>),
+    Draining(BoxFuture<'a, Result<()>>),
+    Done,
+}
+
+/// An owning stream over rows of the current result set of a [`QueryResult`].
+///
+/// Created by [`QueryResult::stream_and_drop`]. Drops the rest of the query result once
+/// exhausted.
+pub struct OwnedResultSetStream<'a, 't, P, R> {
+    state: OwnedResultSetStreamState<'a, 't, P>,
+    __phantom: PhantomData<R>,
+}
+
+impl<'a, 't, P, R> Stream for OwnedResultSetStream<'a, 't, P, R>
+where
+    P: Protocol,
+    R: FromRow + Send + 'static,
+{
+    type Item = Result<R>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // No field here ever points into `Self`'s own memory, so a plain `&mut` projection is
+        // sound.
+        let this = self.get_mut();
+
+        loop {
+            match std::mem::replace(&mut this.state, OwnedResultSetStreamState::Done) {
+                OwnedResultSetStreamState::Done => return Poll::Ready(None),
+                OwnedResultSetStreamState::Ready(query_result) => {
+                    this.state = OwnedResultSetStreamState::Polling(Box::pin(async move {
+                        let mut query_result = query_result;
+                        let row = query_result.get_row().await;
+                        (row, query_result)
+                    }));
+                }
+                OwnedResultSetStreamState::Polling(mut fut) => match fut.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        this.state = OwnedResultSetStreamState::Polling(fut);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready((result, query_result)) => match result {
+                        Ok(Some(row)) => {
+                            this.state = OwnedResultSetStreamState::Ready(query_result);
+                            return Poll::Ready(Some(FromRow::from_row_opt(row).map_err(Into::into)));
+                        }
+                        Ok(None) => {
+                            this.state = OwnedResultSetStreamState::Draining(Box::pin(async move {
+                                let mut query_result = query_result;
+                                query_result.drain().await
+                            }));
+                        }
+                        Err(e) => return Poll::Ready(Some(Err(e))),
+                    },
+                },
+                OwnedResultSetStreamState::Draining(mut fut) => match fut.as_mut().poll(cx) {
+                    Poll::Pending => {
+                        this.state = OwnedResultSetStreamState::Draining(fut);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready(result) => return Poll::Ready(result.err().map(Err)),
+                },
+            }
+        }
+    }
+}
+
+/// A single result set of a (possibly multi-statement) [`QueryResult`].
+///
+/// Produced by [`ResultSets::next`]. Exposes the columns and rows of this result set only.
+pub struct ResultSet<'r, 'a, 't, P> {
+    query_result: &'r mut QueryResult<'a, 't, P>,
+}
+
+impl<'r, 'a, 't, P> ResultSet<'r, 'a, 't, P>
+where
+    P: Protocol,
+{
+    /// Returns a reference to a columns list of this result set.
+    ///
+    /// Empty list means that this result set was never meant to contain rows.
+    pub fn columns_ref(&self) -> &[Column] {
+        self.query_result.columns_ref()
+    }
+
+    /// Returns a copy of a columns list of this result set.
+    pub fn columns(&self) -> Option<Arc<Vec<Column>>> {
+        self.query_result.columns()
+    }
+
+    /// Returns a stream over the rows of this result set.
+    pub fn stream<R>(&mut self) -> ResultSetStream<'_, 'a, 't, P, R>
+    where
+        R: FromRow + Send + 'static,
+    {
+        self.query_result.stream()
+    }
+
+    /// Collects the rows of this result set. See [`QueryResult::collect`] for details.
+    pub async fn collect<R>(&mut self) -> Result<Vec<R>>
+    where
+        R: FromRow + Send + 'static,
+    {
+        self.query_result.collect().await
+    }
+}
+
+/// An iterator over the result sets of a (possibly multi-statement) [`QueryResult`].
+///
+/// Created by [`QueryResult::result_sets`].
+///
+/// Note: same `ConnectionLike`/`Connection` caveat as [`ResultSetStream`] applies here - its
+/// advance-past-a-result-set-boundary behavior isn't covered by a test in this file.
+pub struct ResultSets<'r, 'a, 't, P> {
+    query_result: &'r mut QueryResult<'a, 't, P>,
+    /// Columns of the result set last handed out by `next`. If `query_result` no longer holds
+    /// these very columns, the caller already read that result set up to its boundary.
+    current: Option<Arc<Vec<Column>>>,
+}
+
+impl<'r, 'a, 't, P> ResultSets<'r, 'a, 't, P>
+where
+    P: Protocol,
+{
+    /// Advances to the next result set, if any.
+    ///
+    /// Drains any rows of the current result set left unread before moving on, so it is safe to
+    /// call this without first consuming `ResultSet`'s rows.
+    pub async fn next(&mut self) -> Result<Option<ResultSet<'_, 'a, 't, P>>> {
+        if let Some(current) = self.current.take() {
+            let unfinished = self
+                .query_result
+                .columns()
+                .map_or(false, |columns| Arc::ptr_eq(&columns, &current));
+            if unfinished {
+                self.query_result.drain_current_result_set().await?;
+            }
+        }
+
+        if self.query_result.is_empty() {
+            Ok(None)
+        } else {
+            self.current = self.query_result.columns();
+            Ok(Some(ResultSet {
+                query_result: self.query_result,
+            }))
+        }
+    }
 }
 
 impl crate::Conn {
@@ -361,6 +762,39 @@ impl crate::Conn {
         Ok(())
     }
 
+    /// Sends a command packet, resetting the packet sequence id first.
+    ///
+    /// A command (as opposed to a continuation packet within an already in-flight exchange, e.g.
+    /// a local-infile chunk) always starts a fresh sequence - this is what `COM_STMT_EXECUTE` and
+    /// friends already do, and `COM_RESET_CONNECTION`/`COM_STMT_FETCH` need the same treatment or
+    /// the connection desyncs with a "packets out of order" error.
+    async fn write_command_raw(&mut self, command: &[u8]) -> Result<()> {
+        self.reset_seq_id();
+        self.write_packet(command).await
+    }
+
+    /// Sends `COM_RESET_CONNECTION` and reads the resulting OK/ERR packet.
+    ///
+    /// Unlike a full re-handshake this keeps the TCP/TLS connection open, but resets session
+    /// state on the server - user variables, temporary tables, prepared statements, transaction
+    /// state and the character set - back to their defaults. This is the cheap, correct way to
+    /// sanitize a connection before it's reused, so that session state leaked by a prior
+    /// borrower can't bleed into the next one (e.g. when a connection pool recycles it).
+    ///
+    /// This snapshot doesn't contain a pool module, so nothing calls this automatically yet -
+    /// a pool's recycle path is the intended caller.
+    pub async fn reset_connection(&mut self) -> Result<()> {
+        self.write_command_raw(&[COM_RESET_CONNECTION][..]).await?;
+        let packet = self.read_packet().await?;
+
+        if packet.first() == Some(&0xff) {
+            return Err(parse_err_packet(&*packet, self.capabilities())?.into());
+        }
+
+        self.set_pending_result(None);
+        Ok(())
+    }
+
     /// Will handle local infile packet.
     pub(crate) async fn handle_local_infile(&mut self, packet: &[u8]) -> Result<()> {
         let local_infile = parse_local_infile_packet(&*packet)?;
@@ -386,6 +820,9 @@ impl crate::Conn {
     }
 
     /// Helper that handles result set packet.
+    ///
+    /// Only parses column definitions - this is the same whether rows will follow inline or,
+    /// for a cursor-backed prepared statement, be fetched later via `COM_STMT_FETCH`.
     pub(crate) async fn handle_result_set<P>(&mut self, mut packet: &[u8]) -> Result<()>
     where
         P: Protocol,
@@ -403,4 +840,31 @@ impl crate::Conn {
 
         Ok(())
     }
+
+    /// Sends `COM_STMT_FETCH` for `statement_id`, requesting up to `fetch_size` more rows off an
+    /// already-open server-side cursor.
+    ///
+    /// Doesn't read a response itself - the caller drives that the same way it reads any other
+    /// result set row, since a fetch response is just more row packets followed by the usual
+    /// terminal status packet (with `SERVER_STATUS_CURSOR_EXISTS` set if more rows remain, or
+    /// cleared once the cursor is exhausted).
+    pub(crate) async fn fetch_cursor_rows(&mut self, statement_id: u32, fetch_size: u32) -> Result<()> {
+        self.write_command_raw(&stmt_fetch_payload(statement_id, fetch_size))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stmt_fetch_payload_encodes_command_and_little_endian_fields() {
+        let payload = stmt_fetch_payload(0x04030201, 0x0c0b0a09);
+
+        assert_eq!(
+            payload,
+            vec![COM_STMT_FETCH, 0x01, 0x02, 0x03, 0x04, 0x09, 0x0a, 0x0b, 0x0c]
+        );
+    }
 }